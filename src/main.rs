@@ -9,21 +9,139 @@
 /// the error message will point the root of the problem.
 use num::Complex;
 
+/// Which fractal family to iterate. Each variant changes the recurrence
+/// used to advance 'z', but keeps the same escape test and iteration cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FractalKind {
+    Mandelbrot,
+    Mandelbrot3,
+    BurningShip,
+}
+
+impl FromStr for FractalKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mandelbrot" => Ok(FractalKind::Mandelbrot),
+            "mandelbrot3" => Ok(FractalKind::Mandelbrot3),
+            "burningship" => Ok(FractalKind::BurningShip),
+            _ => Err(format!("unknown fractal kind '{}'", s)),
+        }
+    }
+}
+
 /// Try to determine if 'c' is in the Mandelbrot set, using at most 'limit'
-/// iterations to decide
-fn escape_time(c: Complex<f64>, limit: usize) -> Option<usize> {
-    let mut z = Complex { re: 0.0, im: 0.0 };
+/// iterations to decide. `kind` picks the recurrence, so for `Mandelbrot3`
+/// and `BurningShip` this tests membership in that family's set instead.
+/// On escape, also returns the final `|z|`, which callers can use to
+/// compute a smooth (fractional) iteration count.
+fn escape_time(kind: FractalKind, c: Complex<f64>, limit: usize) -> Option<(usize, f64)> {
+    let mut z: Complex<f64> = Complex { re: 0.0, im: 0.0 };
     for i in 1..limit {
         if z.norm_sqr() > 4.0 {
-            return Some(i);
+            return Some((i, z.norm_sqr().sqrt()));
         }
-        z = z * z + c;
+        z = match kind {
+            FractalKind::Mandelbrot => z * z + c,
+            FractalKind::Mandelbrot3 => z * z * z + c,
+            FractalKind::BurningShip => {
+                let z = Complex {
+                    re: z.re.abs(),
+                    im: z.im.abs(),
+                };
+                z * z + c
+            }
+        };
     }
     None
 }
 
+/// Turn an integer escape count into a fractional one, smoothing out the
+/// banding you get from coloring by raw iteration count alone.
+fn smooth_iteration(count: usize, z_norm: f64) -> f64 {
+    if z_norm.is_finite() && z_norm > 1.0 {
+        count as f64 + 1.0 - (z_norm.ln().ln()) / std::f64::consts::LN_2
+    } else {
+        count as f64
+    }
+}
+
+/// A color gradient that a normalized escape value (in `[0.0, 1.0]`) is
+/// mapped through to produce the final RGB pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Palette {
+    Grayscale,
+    BlueWhite,
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grayscale" => Ok(Palette::Grayscale),
+            "bluewhite" => Ok(Palette::BlueWhite),
+            _ => Err(format!("unknown palette '{}'", s)),
+        }
+    }
+}
+
+/// Map a normalized escape value `t` (clamped to `[0.0, 1.0]`) through
+/// `palette` to produce an RGB triple.
+fn color_at(palette: Palette, t: f64) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    match palette {
+        Palette::Grayscale => {
+            let v = (255.0 * t).round() as u8;
+            [v, v, v]
+        }
+        Palette::BlueWhite => {
+            let v = (255.0 * t).round() as u8;
+            [v, v, 255]
+        }
+    }
+}
+
+/// Which rendering algorithm to use: the per-pixel escape-time map, or
+/// the density-accumulation Buddhabrot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenderMode {
+    EscapeTime,
+    Buddhabrot,
+}
+
+impl FromStr for RenderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "escape" => Ok(RenderMode::EscapeTime),
+            "buddhabrot" => Ok(RenderMode::Buddhabrot),
+            _ => Err(format!("unknown render mode '{}'", s)),
+        }
+    }
+}
+
 use std::str::FromStr;
 
+#[test]
+fn test_fractal_kind_from_str() {
+    assert_eq!(
+        FractalKind::from_str("mandelbrot"),
+        Ok(FractalKind::Mandelbrot)
+    );
+    assert_eq!(
+        FractalKind::from_str("mandelbrot3"),
+        Ok(FractalKind::Mandelbrot3)
+    );
+    assert_eq!(
+        FractalKind::from_str("burningship"),
+        Ok(FractalKind::BurningShip)
+    );
+    assert!(FractalKind::from_str("nope").is_err());
+}
+
 /// Parse the string 's' as a coordinate pair, like "400x600" or "1.0,0.5"
 /// 's' is the "separator" argument
 /// If 's' has the proper form, return 'Some<(x, y)>'
@@ -86,6 +204,51 @@ fn pixel_to_point(
     }
 }
 
+/// The inverse of `pixel_to_point`: given a point on the complex plane,
+/// return the pixel it falls into, or `None` if it lies outside `bounds`.
+fn point_to_pixel(
+    bounds: (usize, usize),
+    point: Complex<f64>,
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Option<(usize, usize)> {
+    let (width, height) = (
+        lower_right.re - upper_left.re,
+        upper_left.im - lower_right.im,
+    );
+    let column = (point.re - upper_left.re) / width * bounds.0 as f64;
+    let row = (upper_left.im - point.im) / height * bounds.1 as f64;
+    if column < 0.0 || row < 0.0 || column >= bounds.0 as f64 || row >= bounds.1 as f64 {
+        return None;
+    }
+    Some((column as usize, row as usize))
+}
+
+#[test]
+fn test_point_to_pixel() {
+    assert_eq!(
+        point_to_pixel(
+            (100, 200),
+            Complex {
+                re: -0.5,
+                im: -0.75
+            },
+            Complex { re: -1.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 }
+        ),
+        Some((25, 175))
+    );
+    assert_eq!(
+        point_to_pixel(
+            (100, 200),
+            Complex { re: 5.0, im: 5.0 },
+            Complex { re: -1.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 }
+        ),
+        None
+    );
+}
+
 #[test]
 fn test_pixel_to_point() {
     assert_eq!(
@@ -103,27 +266,40 @@ fn test_pixel_to_point() {
 }
 
 /// Render a rectanble of the Mandelbrot set int to a buffer of pixels.
+/// The fractal family drawn is the one selected by `kind`.
+/// `pixels` holds 3 bytes (RGB) per pixel, colored by smoothed escape
+/// value through `palette`.
 fn render(
+    kind: FractalKind,
+    palette: Palette,
     pixels: &mut [u8],
     bounds: (usize, usize),
     upper_left: Complex<f64>,
     lower_right: Complex<f64>,
 ) {
-    assert!(pixels.len() == bounds.0 * bounds.1);
+    let limit = 255;
+    assert!(pixels.len() == bounds.0 * bounds.1 * 3);
 
     for row in 0..bounds.1 {
         for column in 0..bounds.0 {
             let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
-            pixels[row * bounds.0 + column] = match escape_time(point, 255) {
-                None => 0,
-                Some(count) => 255 - count as u8,
+            let color = match escape_time(kind, point, limit) {
+                None => [0, 0, 0],
+                Some((count, z_norm)) => {
+                    let mu = smooth_iteration(count, z_norm);
+                    color_at(palette, mu / limit as f64)
+                }
             };
+            let offset = (row * bounds.0 + column) * 3;
+            pixels[offset..offset + 3].copy_from_slice(&color);
         }
     }
 }
 
 /// Simple multithread render
 fn bands(
+    kind: FractalKind,
+    palette: Palette,
     pixels: &mut [u8],
     bounds: (usize, usize),
     upper_left: Complex<f64>,
@@ -131,18 +307,25 @@ fn bands(
     threads: usize,
 ) {
     let row_per_band = bounds.1 / threads + 1;
-    let bands: Vec<&mut [u8]> = pixels.chunks_mut(row_per_band * bounds.0).collect();
+    let bands: Vec<&mut [u8]> = pixels.chunks_mut(row_per_band * bounds.0 * 3).collect();
     crossbeam::scope(|spawner| {
         for (i, band) in bands.into_iter().enumerate() {
             let top = row_per_band * i;
-            let height = band.len() / bounds.0;
+            let height = band.len() / (bounds.0 * 3);
             let band_bounds = (bounds.0, height);
             let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
             let band_lower_right =
                 pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
 
             spawner.spawn(move |_| {
-                render(band, band_bounds, band_upper_left, band_lower_right);
+                render(
+                    kind,
+                    palette,
+                    band,
+                    band_bounds,
+                    band_upper_left,
+                    band_lower_right,
+                );
             });
         }
     })
@@ -153,6 +336,8 @@ use std::sync::Mutex;
 
 /// Task queue
 fn task_queue(
+    kind: FractalKind,
+    palette: Palette,
     pixels: &mut [u8],
     bounds: (usize, usize),
     upper_left: Complex<f64>,
@@ -161,7 +346,7 @@ fn task_queue(
 ) {
     let row_per_band = bounds.1 / threads + 1;
     {
-        let bands = Mutex::new(pixels.chunks_mut(row_per_band * bounds.0).enumerate());
+        let bands = Mutex::new(pixels.chunks_mut(row_per_band * bounds.0 * 3).enumerate());
         crossbeam::scope(|scope| {
             for _ in 0..threads {
                 scope.spawn(|_| loop {
@@ -174,7 +359,7 @@ fn task_queue(
                         }
                         Some((i, band)) => {
                             let top = row_per_band * i;
-                            let height = band.len() / bounds.0;
+                            let height = band.len() / (bounds.0 * 3);
                             let band_bounds = (bounds.0, height);
                             let band_upper_left =
                                 pixel_to_point(bounds, (0, top), upper_left, lower_right);
@@ -185,7 +370,14 @@ fn task_queue(
                                 lower_right,
                             );
 
-                            render(band, band_bounds, band_upper_left, band_lower_right);
+                            render(
+                                kind,
+                                palette,
+                                band,
+                                band_bounds,
+                                band_upper_left,
+                                band_lower_right,
+                            );
                         }
                     }
                 });
@@ -195,10 +387,209 @@ fn task_queue(
     }
 }
 
+use rayon::prelude::*;
+
+/// Multithreaded render that lets Rayon's work-stealing scheduler split
+/// the work, instead of pre-splitting it into fixed bands ourselves.
+fn rayon_render(
+    kind: FractalKind,
+    palette: Palette,
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) {
+    pixels
+        .par_chunks_mut(bounds.0 * 3)
+        .enumerate()
+        .for_each(|(row, band)| {
+            let band_bounds = (bounds.0, 1);
+            let band_upper_left = pixel_to_point(bounds, (0, row), upper_left, lower_right);
+            let band_lower_right =
+                pixel_to_point(bounds, (bounds.0, row + 1), upper_left, lower_right);
+
+            render(
+                kind,
+                palette,
+                band,
+                band_bounds,
+                band_upper_left,
+                band_lower_right,
+            );
+        });
+}
+
+use rand::Rng;
+
+/// Region of the complex plane that Buddhabrot samples are drawn from.
+/// This is independent of the output view rectangle: escaping orbits
+/// that pass through the view overwhelmingly start from `c` outside of
+/// it, so sampling has to cover the whole set, not just what's on screen.
+const SAMPLE_RE: std::ops::Range<f64> = -2.0..1.0;
+const SAMPLE_IM: std::ops::Range<f64> = -1.5..1.5;
+
+/// Run one Buddhabrot sample: iterate `c` under `z = z*z + c`, and if the
+/// orbit escapes before `limit` iterations, replay it from the start and
+/// accumulate every in-bounds intermediate `z` into `grid`.
+fn buddhabrot_sample(
+    grid: &mut [u32],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    c: Complex<f64>,
+    limit: usize,
+) {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    let escaped = (0..limit).any(|_| {
+        z = z * z + c;
+        z.norm_sqr() > 4.0
+    });
+    if !escaped {
+        return;
+    }
+
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    for _ in 0..limit {
+        z = z * z + c;
+        if z.norm_sqr() > 4.0 {
+            break;
+        }
+        if let Some((column, row)) = point_to_pixel(bounds, z, upper_left, lower_right) {
+            grid[row * bounds.0 + column] += 1;
+        }
+    }
+}
+
+/// Normalize a density grid into a grayscale `pixels` buffer (3 bytes per
+/// pixel), applying a gamma curve so faint, low-density regions stay
+/// visible next to the brightest spots.
+fn normalize_grid(grid: &[u32], pixels: &mut [u8]) {
+    let max = grid.iter().copied().max().unwrap_or(0).max(1) as f64;
+    for (i, &count) in grid.iter().enumerate() {
+        let t = (count as f64 / max).powf(1.0 / 2.2);
+        let v = (255.0 * t).round() as u8;
+        let offset = i * 3;
+        pixels[offset..offset + 3].copy_from_slice(&[v, v, v]);
+    }
+}
+
+/// Single-threaded Buddhabrot render: sample `samples` random points `c`
+/// drawn from across the complex plane (see `SAMPLE_RE`/`SAMPLE_IM`),
+/// accumulate escaping orbits into a density grid, then normalize the
+/// grid into `pixels`.
+fn buddhabrot(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    samples: usize,
+    limit: usize,
+) {
+    let mut grid = vec![0u32; bounds.0 * bounds.1];
+    let mut rng = rand::thread_rng();
+    for _ in 0..samples {
+        let c = Complex {
+            re: rng.gen_range(SAMPLE_RE),
+            im: rng.gen_range(SAMPLE_IM),
+        };
+        buddhabrot_sample(&mut grid, bounds, upper_left, lower_right, c, limit);
+    }
+    normalize_grid(&grid, pixels);
+}
+
+/// Multithreaded Buddhabrot render: each thread samples into its own
+/// private density grid, which are all summed together once every
+/// thread finishes, mirroring the band split used by `bands`.
+fn buddhabrot_bands(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    samples: usize,
+    limit: usize,
+    threads: usize,
+) {
+    let samples_per_thread = samples / threads + 1;
+    let grids: Vec<Vec<u32>> = crossbeam::scope(|spawner| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                spawner.spawn(move |_| {
+                    let mut grid = vec![0u32; bounds.0 * bounds.1];
+                    let mut rng = rand::thread_rng();
+                    for _ in 0..samples_per_thread {
+                        let c = Complex {
+                            re: rng.gen_range(SAMPLE_RE),
+                            im: rng.gen_range(SAMPLE_IM),
+                        };
+                        buddhabrot_sample(&mut grid, bounds, upper_left, lower_right, c, limit);
+                    }
+                    grid
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    })
+    .unwrap();
+
+    let mut total = vec![0u32; bounds.0 * bounds.1];
+    for grid in grids {
+        for (t, g) in total.iter_mut().zip(grid) {
+            *t += g;
+        }
+    }
+    normalize_grid(&total, pixels);
+}
+
+use std::fs::File;
+use std::io::Write;
+
+/// Write a binary PNM image (`P5`/PGM for `channels == 1`, `P6`/PPM for
+/// `channels == 3`) to `filename`. This is a dependency-light fallback to
+/// the `image` crate's PNG encoder: no compression, no parsing, just the
+/// header followed by the raw sample bytes.
+fn write_pnm(
+    filename: &str,
+    pixels: &[u8],
+    bounds: (usize, usize),
+    channels: usize,
+) -> std::io::Result<()> {
+    let magic = if channels == 1 { "P5" } else { "P6" };
+    let mut file = File::create(filename)?;
+    writeln!(file, "{}", magic)?;
+    writeln!(file, "{} {}", bounds.0, bounds.1)?;
+    writeln!(file, "255")?;
+    file.write_all(pixels)
+}
+
+use image::ColorType;
+
+/// Write the rendered `pixels` buffer (RGB, 3 bytes per pixel) out to
+/// `filename`, picking the encoder from the file extension: `.pgm`
+/// writes a grayscale PNM (taking the red channel, which for the
+/// grayscale palette equals green and blue), `.ppm` writes a color PNM,
+/// and anything else is handed to the `image` crate (e.g. `.png`).
+fn write_image(filename: &str, pixels: &[u8], bounds: (usize, usize)) -> std::io::Result<()> {
+    if filename.ends_with(".pgm") {
+        let gray: Vec<u8> = pixels.chunks(3).map(|rgb| rgb[0]).collect();
+        write_pnm(filename, &gray, bounds, 1)
+    } else if filename.ends_with(".ppm") {
+        write_pnm(filename, pixels, bounds, 3)
+    } else {
+        image::save_buffer(
+            filename,
+            pixels,
+            bounds.0 as u32,
+            bounds.1 as u32,
+            ColorType::Rgb8,
+        )
+        .map_err(std::io::Error::other)
+    }
+}
+
 use std::env;
 use std::time::{Duration, Instant};
 
-fn main() {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut args: Vec<String> = env::args().collect();
 
     if args.len() == 1 {
@@ -208,21 +599,71 @@ fn main() {
             "2000x1500".into(),
             "-1.20,0.35".into(),
             "-1.0,0.20".into(),
+            "mandelbrot".into(),
+            "grayscale".into(),
         ];
-    } else if args.len() != 5 {
-        eprintln!("Usage: {} File Pixels Upper_Left Lower_Right", args[0]);
+    } else if args.len() < 5 || args.len() > 9 {
+        eprintln!(
+            "Usage: {} File Pixels Upper_Left Lower_Right [Fractal] [Palette] [Mode] [Samples]",
+            args[0]
+        );
         eprintln!(
-            "Example: {} mandel.png 1000x750 -1.20,0.35 -1,0.20",
+            "Example: {} mandel.png 1000x750 -1.20,0.35 -1,0.20 mandelbrot grayscale escape",
             args[0]
         );
+        eprintln!("Fractal: mandelbrot (default), mandelbrot3, burningship");
+        eprintln!("Palette: grayscale (default), bluewhite");
+        eprintln!("Mode: escape (default), buddhabrot");
+        eprintln!("Samples: number of Buddhabrot samples (default 2000000)");
         std::process::exit(1);
     }
 
     let bounds = parse_pair(&args[2], 'x').expect("error parsing image dimensions");
     let upper_left = parse_complex(&args[3]).expect("error parsing upper left corner point");
     let lower_right = parse_complex(&args[4]).expect("error parsing lower right corner point");
+    let kind = match args.get(5) {
+        Some(kind) => FractalKind::from_str(kind).expect("error parsing fractal kind"),
+        None => FractalKind::Mandelbrot,
+    };
+    let palette = match args.get(6) {
+        Some(palette) => Palette::from_str(palette).expect("error parsing palette"),
+        None => Palette::Grayscale,
+    };
+    let mode = match args.get(7) {
+        Some(mode) => RenderMode::from_str(mode).expect("error parsing render mode"),
+        None => RenderMode::EscapeTime,
+    };
+    let samples: usize = match args.get(8) {
+        Some(samples) => samples.parse().expect("error parsing sample count"),
+        None => 2_000_000,
+    };
+
+    let mut pixels = vec![0; bounds.0 * bounds.1 * 3];
+
+    if mode == RenderMode::Buddhabrot {
+        let start = Instant::now();
+        buddhabrot(&mut pixels, bounds, upper_left, lower_right, samples, 255);
+        println!("buddhabrot {} samples: {:?}", samples, start.elapsed());
 
-    let mut pixels = vec![0; bounds.0 * bounds.1];
+        let num_threads = num_cpus::get();
+        let start = Instant::now();
+        buddhabrot_bands(
+            &mut pixels,
+            bounds,
+            upper_left,
+            lower_right,
+            samples,
+            255,
+            num_threads,
+        );
+        println!(
+            "buddhabrot {} samples, {} threads: {:?}",
+            samples,
+            num_threads,
+            start.elapsed()
+        );
+        return write_image(&args[1], &pixels, bounds).map_err(Into::into);
+    }
 
     // Multithreading test part
     // num_cpus
@@ -238,7 +679,7 @@ fn main() {
     for _ in 0..iteration {
         let start = Instant::now();
 
-        render(&mut pixels, bounds, upper_left, lower_right);
+        render(kind, palette, &mut pixels, bounds, upper_left, lower_right);
 
         let duration = start.elapsed();
         total_duration += duration;
@@ -253,6 +694,8 @@ fn main() {
         let start = Instant::now();
 
         bands(
+            kind,
+            palette,
             &mut pixels,
             bounds,
             upper_left,
@@ -272,6 +715,8 @@ fn main() {
         let start = Instant::now();
 
         bands(
+            kind,
+            palette,
             &mut pixels,
             bounds,
             upper_left,
@@ -292,6 +737,8 @@ fn main() {
         let start = Instant::now();
 
         task_queue(
+            kind,
+            palette,
             &mut pixels,
             bounds,
             upper_left,
@@ -311,6 +758,8 @@ fn main() {
         let start = Instant::now();
 
         task_queue(
+            kind,
+            palette,
             &mut pixels,
             bounds,
             upper_left,
@@ -323,4 +772,22 @@ fn main() {
     }
     let average_duration = total_duration / iteration;
     println!("task queue {} {:?}", num_physical_cores, average_duration);
+
+    // -------------
+    // Rayon
+    let mut total_duration = Duration::new(0, 0);
+    for _ in 0..iteration {
+        let start = Instant::now();
+
+        rayon_render(kind, palette, &mut pixels, bounds, upper_left, lower_right);
+
+        let duration = start.elapsed();
+        total_duration += duration;
+    }
+    let average_duration = total_duration / iteration;
+    println!("rayon {:?}", average_duration);
+
+    write_image(&args[1], &pixels, bounds)?;
+
+    Ok(())
 }